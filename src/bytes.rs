@@ -1,13 +1,13 @@
 use core::{fmt, fmt::Debug};
 use std::{
     borrow::Cow,
+    cell::Cell,
     hash::{Hash, Hasher},
     marker::PhantomData,
     mem::ManuallyDrop,
 };
 
 /// A wrapper around raw bytes
-#[derive(Eq, PartialOrd, Ord)]
 pub struct Bytes<'a> {
     /// The inner data
     data: BytesInner,
@@ -15,18 +15,35 @@ pub struct Bytes<'a> {
     _lt: PhantomData<&'a [u8]>,
 }
 
+/// Number of bytes [`BytesInner::Inline`] can hold directly in the enum
+/// without heap-allocating, chosen to keep `Inline` no bigger than `Shared`
+const INLINE_CAPACITY: usize = 14;
+
 /// The inner data of [`Bytes`]
 ///
 /// Instead of using `&[u8]` and `Vec<u8>` for the variants,
 /// we use raw pointers and a `u32` for the length.
 /// This is to keep the size of the enum to 16 (on 64-bit machines),
-/// which is the same as if this was just `struct Bytes<'a>(&'a [u8])`
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+/// which is the same as if this was just `struct Bytes<'a>(&'a [u8])`.
+/// `Shared` carries an extra pointer for the refcount, so it grows past
+/// that goal, but it's still far cheaper than re-allocating on every clone.
 enum BytesInner {
     /// Borrowed bytes
     Borrowed(*const u8, u32),
     /// Owned bytes
     Owned(*mut u8, u32),
+    /// Bytes backed by a reference-counted allocation.
+    ///
+    /// The third field points to a heap-allocated refcount shared by every
+    /// clone of this value; the data itself is freed once it drops to zero.
+    /// A plain `Cell<usize>` is enough (no atomics): `BytesInner` has
+    /// raw-pointer variants, so `Bytes` is already `!Send`/`!Sync` and this can never
+    /// be touched from more than one thread at a time, unlike `Arc`.
+    Shared(*const u8, u32, *const Cell<usize>),
+    /// Small owned values stored directly in the enum, avoiding a heap
+    /// allocation entirely. The second field is how many leading bytes of
+    /// the array are valid (always `<= INLINE_CAPACITY`).
+    Inline([u8; INLINE_CAPACITY], u8),
 }
 
 impl<'a> PartialEq for Bytes<'a> {
@@ -38,6 +55,26 @@ impl<'a> PartialEq for Bytes<'a> {
     }
 }
 
+impl<'a> Eq for Bytes<'a> {}
+
+impl<'a> PartialOrd for Bytes<'a> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Bytes<'a> {
+    // `PartialOrd`/`Ord` must be implemented manually too, just like `PartialEq`/`Hash`:
+    // deriving them on `BytesInner` compares by discriminant then by raw
+    // pointer/byte-array, which would let `a == b` (content-based) disagree
+    // with `a.cmp(&b) != Equal`, breaking the `Eq`/`Ord` contract
+    #[inline]
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
 impl<'a> Hash for Bytes<'a> {
     #[inline]
     fn hash<H: Hasher>(&self, state: &mut H) {
@@ -65,11 +102,34 @@ impl<'a> Clone for Bytes<'a> {
                     _lt: PhantomData,
                 }
             }
+            BytesInner::Shared(data, len, count) => {
+                // Cloning a shared value is just a refcount bump, no allocation.
+                // Like `Rc`'s clone, refuse to silently wrap back through zero
+                // with live handles still outstanding (this panics; `Rc` aborts).
+                let incremented = unsafe { (**count).get() }
+                    .checked_add(1)
+                    .expect("Bytes shared refcount overflowed");
+                unsafe { (**count).set(incremented) };
+                Bytes {
+                    data: BytesInner::Shared(*data, *len, *count),
+                    _lt: PhantomData,
+                }
+            }
+            BytesInner::Inline(data, len) => {
+                // Inline data is just a plain copy, no pointers involved
+                Bytes {
+                    data: BytesInner::Inline(*data, *len),
+                    _lt: PhantomData,
+                }
+            }
         }
     }
 }
 
 impl<'a> From<&'a str> for Bytes<'a> {
+    /// ## Panics
+    /// Panics in debug builds if `s.len()` does not fit in a `u32`.
+    /// Use [`Bytes::try_from_slice`] to handle this case without panicking.
     #[inline]
     fn from(s: &'a str) -> Self {
         <Self as From<&'a [u8]>>::from(s.as_bytes())
@@ -77,8 +137,13 @@ impl<'a> From<&'a str> for Bytes<'a> {
 }
 
 impl<'a> From<&'a [u8]> for Bytes<'a> {
+    /// ## Panics
+    /// Panics in debug builds if `s.len()` does not fit in a `u32`.
+    /// Use [`Bytes::try_from_slice`] to handle this case without panicking.
     #[inline]
     fn from(s: &'a [u8]) -> Self {
+        debug_assert!(s.len() <= u32::MAX as usize, "slice length overflows u32");
+
         Bytes {
             data: BytesInner::Borrowed(s.as_ptr(), s.len() as u32),
             _lt: PhantomData,
@@ -86,6 +151,11 @@ impl<'a> From<&'a [u8]> for Bytes<'a> {
     }
 }
 
+// Note: there's no `impl TryFrom<&'a [u8]> for Bytes<'a>` here, even though
+// that's the more idiomatic trait to implement — it would conflict with the
+// blanket `impl<T, U: Into<T>> TryFrom<U> for T` in core, since we also have
+// `From<&'a [u8]>`. `Bytes::try_from_slice` is the checked constructor instead.
+
 /// Converts `Bytes` raw parts to a slice
 #[inline]
 unsafe fn compact_bytes_to_slice<'a>(ptr: *const u8, l: u32) -> &'a [u8] {
@@ -119,6 +189,14 @@ unsafe fn clone_compact_bytes_parts(ptr: *mut u8, len: u32) -> (*mut u8, u32) {
     boxed_slice_to_compact_parts(compact_bytes_to_boxed_slice(ptr, len).clone())
 }
 
+/// Allocates a fresh refcount (initialized to 1) for a shared allocation and
+/// returns it as a raw pointer the caller is responsible for freeing once the
+/// count reaches zero
+#[inline]
+fn new_shared_refcount() -> *const Cell<usize> {
+    Box::into_raw(Box::new(Cell::new(1)))
+}
+
 // Custom `Debug` trait is implemented which displays the data as a UTF8 string,
 // to make it easier to read for humans when logging
 impl<'a> Debug for Bytes<'a> {
@@ -140,6 +218,8 @@ impl<'a> Bytes<'a> {
         match &self.data {
             BytesInner::Borrowed(b, l) => unsafe { compact_bytes_to_slice(*b, *l) },
             BytesInner::Owned(o, l) => unsafe { compact_bytes_to_slice(*o, *l) },
+            BytesInner::Shared(s, l, _) => unsafe { compact_bytes_to_slice(*s, *l) },
+            BytesInner::Inline(data, len) => &data[..*len as usize],
         }
     }
 
@@ -156,14 +236,70 @@ impl<'a> Bytes<'a> {
     }
 
     /// Returns a read-only raw pointer to the inner data
+    ///
+    /// For `Borrowed`/`Owned`/`Shared`, the returned pointer stays valid even
+    /// after `self` is moved, since the pointee lives independently of the
+    /// `Bytes` value itself. For `Inline`, the bytes live inside `self`, so
+    /// moving it (e.g. into a `Vec` or out of a function) relocates the data
+    /// and invalidates any pointer obtained before the move.
     #[inline]
     pub fn as_ptr(&self) -> *const u8 {
         match &self.data {
             BytesInner::Borrowed(b, _) => *b,
             BytesInner::Owned(o, _) => *o,
+            BytesInner::Shared(s, _, _) => *s,
+            BytesInner::Inline(data, _) => data.as_ptr(),
+        }
+    }
+
+    /// Promotes this value into the reference-counted `Shared` representation,
+    /// so that future clones only bump a refcount instead of copying the data
+    ///
+    /// If `self` is already `Shared`, it is returned with its refcount unchanged. If it is
+    /// `Owned`, the existing allocation is reused as-is. If it is `Borrowed`,
+    /// the data is copied once into a fresh shared allocation.
+    pub fn into_shared(self) -> Bytes<'a> {
+        let this = ManuallyDrop::new(self);
+        let data = match &this.data {
+            BytesInner::Borrowed(ptr, len) => {
+                let slice = unsafe { compact_bytes_to_slice(*ptr, *len) };
+                let boxed: Box<[u8]> = Box::from(slice);
+                let (ptr, len) = unsafe { boxed_slice_to_compact_parts(boxed) };
+                BytesInner::Shared(ptr, len, new_shared_refcount())
+            }
+            BytesInner::Owned(ptr, len) => BytesInner::Shared(*ptr, *len, new_shared_refcount()),
+            // `into_shared` consumes `self`; a value that's already `Shared`
+            // just carries its existing strong reference over unchanged -
+            // bumping the count here would leak one reference per call
+            BytesInner::Shared(ptr, len, count) => BytesInner::Shared(*ptr, *len, *count),
+            BytesInner::Inline(data, len) => {
+                let boxed: Box<[u8]> = Box::from(&data[..*len as usize]);
+                let (ptr, len) = unsafe { boxed_slice_to_compact_parts(boxed) };
+                BytesInner::Shared(ptr, len, new_shared_refcount())
+            }
+        };
+
+        Bytes {
+            data,
+            _lt: PhantomData,
         }
     }
 
+    /// Builds a borrowed `Bytes` from a slice, checking that its length fits in a `u32`
+    /// instead of silently truncating it like the `From<&[u8]>` impl does in release builds
+    pub fn try_from_slice(s: &'a [u8]) -> Result<Self, SetBytesError> {
+        const MAX: usize = u32::MAX as usize;
+
+        if s.len() > MAX {
+            return Err(SetBytesError::LengthOverflow);
+        }
+
+        Ok(Bytes {
+            data: BytesInner::Borrowed(s.as_ptr(), s.len() as u32),
+            _lt: PhantomData,
+        })
+    }
+
     /// Sets the inner data to the given bytes
     pub fn set<B: Into<Box<[u8]>>>(&mut self, data: B) -> Result<(), SetBytesError> {
         const MAX: usize = u32::MAX as usize;
@@ -181,12 +317,22 @@ impl<'a> Bytes<'a> {
 
     /// Sets the inner data to the given bytes without checking for validity of the data
     ///
+    /// Data that fits in [`INLINE_CAPACITY`] bytes is stored inline with no
+    /// allocation; anything longer is heap-allocated as `Owned`.
+    ///
     /// ## Safety
     /// - Once `data` is converted to a `Box<[u8]>`, its length must not be greater than u32::MAX
     #[inline]
     pub unsafe fn set_unchecked<B: Into<Box<[u8]>>>(&mut self, data: B) {
         let data = <B as Into<Box<[u8]>>>::into(data);
 
+        if data.len() <= INLINE_CAPACITY {
+            let mut inline = [0u8; INLINE_CAPACITY];
+            inline[..data.len()].copy_from_slice(&data);
+            self.data = BytesInner::Inline(inline, data.len() as u8);
+            return;
+        }
+
         let (ptr, len) = boxed_slice_to_compact_parts(data);
 
         self.data = BytesInner::Owned(ptr, len);
@@ -201,15 +347,290 @@ pub enum SetBytesError {
 
 impl Drop for BytesInner {
     fn drop(&mut self) {
-        // we only need to deallocate if we own the data
-        // if we don't, just do nothing
-        if let BytesInner::Owned(ptr, len) = self {
-            let ptr = *ptr;
-            let len = *len as usize;
+        match self {
+            BytesInner::Owned(ptr, len) => {
+                let ptr = *ptr;
+                let len = *len as usize;
+
+                // carefully reconstruct a `Box<[u8]>` from the raw pointer and length
+                // and immediately drop it to free memory
+                unsafe { drop(Vec::from_raw_parts(ptr, len, len).into_boxed_slice()) };
+            }
+            BytesInner::Shared(ptr, len, count) => {
+                // Mirrors `Rc`'s drop: only the clone that takes the count to
+                // zero frees the allocation
+                let remaining = unsafe { (**count).get() }
+                    .checked_sub(1)
+                    .expect("Bytes shared refcount underflowed");
+                unsafe { (**count).set(remaining) };
+                if remaining != 0 {
+                    return;
+                }
+
+                let ptr = *ptr as *mut u8;
+                let len = *len as usize;
+
+                unsafe {
+                    drop(Vec::from_raw_parts(ptr, len, len).into_boxed_slice());
+                    drop(Box::from_raw(*count as *mut Cell<usize>));
+                }
+            }
+            // we only need to deallocate if we own the data
+            // if we don't, just do nothing
+            BytesInner::Borrowed(..) | BytesInner::Inline(..) => {}
+        }
+    }
+}
+
+/// A zero-copy cursor over a [`Bytes`], for consuming it incrementally
+/// (e.g. while tokenizing) instead of re-slicing by hand
+///
+/// Modeled after the `remaining`/`chunk`/`advance` shape of the `bytes` crate's
+/// `Buf` trait, but specialized to `Bytes` so `take`/`split_at` can hand back
+/// sub-views that share the same backing instead of copying.
+pub struct BytesCursor<'a> {
+    bytes: &'a Bytes<'a>,
+    pos: usize,
+}
+
+impl<'a> BytesCursor<'a> {
+    /// Creates a cursor starting at the beginning of `bytes`
+    #[inline]
+    pub fn new(bytes: &'a Bytes<'a>) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns the number of unconsumed bytes left in the cursor
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.bytes.as_bytes().len() - self.pos
+    }
+
+    /// Returns the unconsumed tail of the underlying data
+    #[inline]
+    pub fn chunk(&self) -> &[u8] {
+        &self.bytes.as_bytes()[self.pos..]
+    }
+
+    /// Advances the cursor by `cnt` bytes
+    ///
+    /// ## Panics
+    /// Panics if `cnt` is greater than [`BytesCursor::remaining`]
+    #[inline]
+    pub fn advance(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.remaining(),
+            "cannot advance the cursor past the end of the buffer"
+        );
+        self.pos += cnt;
+    }
+
+    /// Reads and consumes a single byte
+    ///
+    /// ## Panics
+    /// Panics if the cursor has no remaining bytes
+    #[inline]
+    pub fn get_u8(&mut self) -> u8 {
+        let byte = self.chunk()[0];
+        self.advance(1);
+        byte
+    }
+
+    /// Consumes the next `n` bytes and returns them as a borrowed [`Bytes`]
+    /// that shares the same backing as the cursor's source, with no copy
+    ///
+    /// ## Panics
+    /// Panics if `n` is greater than [`BytesCursor::remaining`]
+    pub fn take(&mut self, n: usize) -> Bytes<'a> {
+        let sub = self.sub_bytes(self.pos, n);
+        self.advance(n);
+        sub
+    }
+
+    /// Splits the unconsumed data at `n`, returning `(before, after)` as two
+    /// borrowed [`Bytes`] sharing the same backing, without advancing the cursor
+    ///
+    /// ## Panics
+    /// Panics if `n` is greater than [`BytesCursor::remaining`]
+    pub fn split_at(&self, n: usize) -> (Bytes<'a>, Bytes<'a>) {
+        let before = self.sub_bytes(self.pos, n);
+        let after = self.sub_bytes(self.pos + n, self.remaining() - n);
+        (before, after)
+    }
+
+    /// Builds a borrowed `Bytes` pointing `len` bytes into the source data
+    /// starting at `start`, without copying
+    fn sub_bytes(&self, start: usize, len: usize) -> Bytes<'a> {
+        let full = self.bytes.as_bytes();
+        assert!(
+            start + len <= full.len(),
+            "sub-view out of bounds of the source buffer"
+        );
+
+        // SAFETY: `start + len <= full.len()` was just checked above, and the
+        // data `full` points into is kept alive for 'a by the `&'a Bytes<'a>`
+        // this cursor holds, even if that `Bytes` is itself `Owned`
+        let ptr = unsafe { full.as_ptr().add(start) };
 
-            // carefully reconstruct a `Box<[u8]>` from the raw pointer and length
-            // and immediately drop it to free memory
-            unsafe { drop(Vec::from_raw_parts(ptr, len, len).into_boxed_slice()) };
+        Bytes {
+            data: BytesInner::Borrowed(ptr, len as u32),
+            _lt: PhantomData,
         }
     }
 }
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::Bytes;
+    use core::fmt;
+    use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+
+    impl<'a> Serialize for Bytes<'a> {
+        /// Serializes as a plain byte sequence, following the `serde_bytes`
+        /// convention so compact formats (e.g. bincode) don't pay for a
+        /// per-element sequence encoding
+        #[inline]
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a byte sequence")
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+
+    impl<'de, 'a> Deserialize<'de> for Bytes<'a> {
+        /// Always produces an owned value (`Inline` for short data, `Owned`
+        /// otherwise), since the decoded bytes don't borrow from anything
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let bytes = deserializer.deserialize_byte_buf(BytesVisitor)?;
+
+            let mut this = Bytes::from(&[][..]);
+            this.set(bytes)
+                .map_err(|_| serde::de::Error::custom("byte sequence is too long for `Bytes`"))?;
+
+            Ok(this)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an `Owned` `Bytes` from data long enough to never be routed
+    /// through `Inline` (longer than `INLINE_CAPACITY`)
+    fn owned(data: &[u8]) -> Bytes<'static> {
+        assert!(data.len() > INLINE_CAPACITY);
+        let mut bytes = Bytes::from(&[][..]);
+        bytes.set(data.to_vec()).unwrap();
+        bytes
+    }
+
+    fn shared_refcount(b: &Bytes) -> usize {
+        match &b.data {
+            BytesInner::Shared(_, _, count) => unsafe { (**count).get() },
+            _ => panic!("expected BytesInner::Shared"),
+        }
+    }
+
+    #[test]
+    fn clone_bumps_refcount_and_drop_decrements_it() {
+        let shared = owned(b"a value long enough to heap allocate").into_shared();
+        assert_eq!(shared_refcount(&shared), 1);
+
+        let clone = shared.clone();
+        assert_eq!(shared_refcount(&shared), 2);
+
+        drop(clone);
+        assert_eq!(shared_refcount(&shared), 1);
+    }
+
+    #[test]
+    fn into_shared_on_an_already_shared_value_does_not_leak_a_reference() {
+        let shared1 = owned(b"another value long enough to heap allocate").into_shared();
+        assert_eq!(shared_refcount(&shared1), 1);
+
+        // Regression test for the chunk0-1 leak: `into_shared` consumes
+        // `self`, so promoting an already-`Shared` value must carry the
+        // existing strong reference over unchanged, not bump it.
+        let shared2 = shared1.into_shared();
+        assert_eq!(shared_refcount(&shared2), 1);
+    }
+
+    #[test]
+    fn equal_content_across_variants_implies_equal_ordering() {
+        let borrowed = Bytes::from(b"tiny".as_slice());
+        let mut inline = Bytes::from(&[][..]);
+        inline.set(b"tiny".to_vec()).unwrap();
+
+        assert_eq!(borrowed, inline);
+        assert_eq!(borrowed.cmp(&inline), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn set_routes_short_data_inline_and_long_data_owned() {
+        let mut short = Bytes::from(&[][..]);
+        short.set(b"tiny".to_vec()).unwrap();
+        assert!(matches!(short.data, BytesInner::Inline(..)));
+        assert_eq!(short.as_bytes(), b"tiny");
+
+        let long = owned(b"this value is long enough to not fit inline");
+        assert!(matches!(long.data, BytesInner::Owned(..)));
+    }
+
+    #[test]
+    fn try_from_slice_accepts_a_valid_length() {
+        let bytes = Bytes::try_from_slice(b"tiny").unwrap();
+        assert_eq!(bytes.as_bytes(), b"tiny");
+    }
+
+    #[test]
+    fn cursor_take_and_split_at_share_the_source_backing() {
+        let data = Bytes::from(b"hello world".as_slice());
+        let mut cursor = BytesCursor::new(&data);
+        assert_eq!(cursor.remaining(), 11);
+
+        let hello = cursor.take(5);
+        assert_eq!(hello.as_bytes(), b"hello");
+        assert_eq!(hello.as_ptr(), data.as_ptr());
+        assert_eq!(cursor.get_u8(), b' ');
+
+        let (before, after) = cursor.split_at(3);
+        assert_eq!(before.as_bytes(), b"wor");
+        assert_eq!(after.as_bytes(), b"ld");
+        assert_eq!(before.as_ptr(), data.as_ptr().wrapping_add(6));
+        assert_eq!(after.as_ptr(), data.as_ptr().wrapping_add(9));
+    }
+}